@@ -1,4 +1,14 @@
-use gl::GLuint;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use gl::{self, GLint, GLuint};
+use image::{self, DynamicImage, GenericImageView};
+use log::{debug, error, warn};
 
 /// OpenGL texture, use `ReadOnlyWindow::create_texture` to create a texture
 ///
@@ -13,46 +23,319 @@ pub struct Texture {
     /// Dimensions (width, height in pixels).
     pub width: usize,
     pub height: usize,
+    /// Format, filtering, wrapping and swizzle this texture was created with
+    pub descriptor: TextureDescriptor,
     /// A reference-counted pointer to the OpenGL context (so that the texture can be deleted in the destructor)
     pub gl_context: Rc<Gl>,
 }
 
+/// Describes the storage format, filtering, wrap modes, and optional channel
+/// swizzle of a `Texture`. Build one from a `TextureFormat` via
+/// `TextureFormat::descriptor()`, or construct manually for less common cases.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDescriptor {
+    pub internal_format: GLint,
+    pub pixel_format: GLuint,
+    pub pixel_type: GLuint,
+    pub min_filter: GLint,
+    pub mag_filter: GLint,
+    pub wrap_s: GLint,
+    pub wrap_t: GLint,
+    /// Per-channel swizzle applied via `GL_TEXTURE_SWIZZLE_R/G/B/A`, in `[R, G, B, A]`
+    /// order. Used to emulate a format the platform has no native upload path for,
+    /// e.g. uploading BGRA data as RGBA and swapping red and blue back on sample, so
+    /// shaders read the channels in RGBA order.
+    pub swizzle: Option<[GLuint; 4]>,
+}
+
+impl Default for TextureDescriptor {
+    fn default() -> Self {
+        Self {
+            internal_format: gl::RGBA8 as GLint,
+            pixel_format: gl::RGBA,
+            pixel_type: gl::UNSIGNED_BYTE,
+            min_filter: gl::NEAREST as GLint,
+            mag_filter: gl::NEAREST as GLint,
+            wrap_s: gl::CLAMP_TO_EDGE as GLint,
+            wrap_t: gl::CLAMP_TO_EDGE as GLint,
+            swizzle: None,
+        }
+    }
+}
+
+/// Common texture formats, resolved to a concrete `TextureDescriptor` via `descriptor()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    /// 8 bits per channel, RGBA order
+    Rgba8,
+    /// 8 bits per channel, BGRA order - emulated via an RGBA upload + swizzle
+    /// on platforms (e.g. GLES) without native `GL_BGRA` support
+    Bgra8,
+    /// Single-channel 8-bit format, useful for masks and glyph atlases. Odd-width
+    /// uploads rely on `Texture::create`/`upload_sub_region` setting `UNPACK_ALIGNMENT`
+    /// to 1 - without it, rows whose byte width isn't a multiple of 4 come out skewed.
+    R8,
+    /// Two-channel 8-bit format - see the `R8` note on unpack alignment for odd widths
+    Rg8,
+    /// 16-bit float RGBA, for HDR render targets
+    Rgba16F,
+    /// 24-bit depth format, for depth-only render targets
+    Depth24,
+}
+
+impl TextureFormat {
+
+    /// Resolves this format to a `TextureDescriptor`. `supports_native_bgra` should
+    /// reflect whether the current GL context can sample `GL_BGRA` directly (true on
+    /// desktop GL, usually false on GLES without `EXT_texture_format_BGRA8888`) - when
+    /// `false`, `Bgra8` is emulated by uploading RGBA data and swizzling it on sample.
+    pub fn descriptor(self, supports_native_bgra: bool) -> TextureDescriptor {
+        let defaults = TextureDescriptor::default();
+        match self {
+            TextureFormat::Rgba8 => TextureDescriptor {
+                internal_format: gl::RGBA8 as GLint,
+                pixel_format: gl::RGBA,
+                pixel_type: gl::UNSIGNED_BYTE,
+                swizzle: None,
+                ..defaults
+            },
+            TextureFormat::Bgra8 if supports_native_bgra => TextureDescriptor {
+                internal_format: gl::RGBA8 as GLint,
+                pixel_format: gl::BGRA,
+                pixel_type: gl::UNSIGNED_BYTE,
+                swizzle: None,
+                ..defaults
+            },
+            TextureFormat::Bgra8 => TextureDescriptor {
+                internal_format: gl::RGBA8 as GLint,
+                pixel_format: gl::RGBA,
+                pixel_type: gl::UNSIGNED_BYTE,
+                swizzle: Some([gl::BLUE, gl::GREEN, gl::RED, gl::ALPHA]),
+                ..defaults
+            },
+            TextureFormat::R8 => TextureDescriptor {
+                internal_format: gl::R8 as GLint,
+                pixel_format: gl::RED,
+                pixel_type: gl::UNSIGNED_BYTE,
+                swizzle: None,
+                ..defaults
+            },
+            TextureFormat::Rg8 => TextureDescriptor {
+                internal_format: gl::RG8 as GLint,
+                pixel_format: gl::RG,
+                pixel_type: gl::UNSIGNED_BYTE,
+                swizzle: None,
+                ..defaults
+            },
+            TextureFormat::Rgba16F => TextureDescriptor {
+                internal_format: gl::RGBA16F as GLint,
+                pixel_format: gl::RGBA,
+                pixel_type: gl::FLOAT,
+                swizzle: None,
+                ..defaults
+            },
+            TextureFormat::Depth24 => TextureDescriptor {
+                internal_format: gl::DEPTH_COMPONENT24 as GLint,
+                pixel_format: gl::DEPTH_COMPONENT,
+                pixel_type: gl::UNSIGNED_INT,
+                swizzle: None,
+                ..defaults
+            },
+        }
+    }
+}
+
+/// Error returned by `Texture::from_image_bytes` when the `image` crate fails to
+/// decode the given bytes (unrecognized format, truncated data, etc.)
+#[derive(Debug)]
+pub enum TextureImageError {
+    Decode(image::ImageError),
+}
+
 impl Texture {
 
-    /// Note: Creates a new texture (calls `gen_textures()`)
+    /// Note: Creates a new RGBA8 texture (calls `gen_textures()`). Shorthand for
+    /// `Texture::with_descriptor(gl_context, width, height, TextureDescriptor::default())`.
     pub fn new(gl_context: Rc<Gl>, width: usize, height: usize) -> Self {
+        Self::with_descriptor(gl_context, width, height, TextureDescriptor::default())
+    }
+
+    /// Like `new`, but lets the caller pick the internal/pixel format, filtering, wrap
+    /// modes, and an optional channel swizzle - see `TextureFormat::descriptor()` for
+    /// ready-made descriptors covering the common formats.
+    pub fn with_descriptor(gl_context: Rc<Gl>, width: usize, height: usize, descriptor: TextureDescriptor) -> Self {
+        Self::create(gl_context, width, height, descriptor, None, false)
+    }
+
+    /// Decodes `bytes` (PNG, JPEG, or anything else the `image` crate supports) and
+    /// uploads the result as a new texture, picking the internal/pixel format from the
+    /// decoded color type (luma -> `R8`, luma+alpha -> `RG8`, everything else -> `RGBA8`).
+    pub fn from_image_bytes(gl_context: Rc<Gl>, bytes: &[u8], generate_mipmaps: bool) -> Result<Self, TextureImageError> {
+        let image = image::load_from_memory(bytes).map_err(TextureImageError::Decode)?;
+        let (width, height) = image.dimensions();
+
+        let (format, pixels): (TextureFormat, Vec<u8>) = match image {
+            DynamicImage::ImageLuma8(buffer) => (TextureFormat::R8, buffer.into_raw()),
+            DynamicImage::ImageLumaA8(buffer) => (TextureFormat::Rg8, buffer.into_raw()),
+            other => (TextureFormat::Rgba8, other.to_rgba().into_raw()),
+        };
+
+        Ok(Self::from_raw(gl_context, width as usize, height as usize, format.descriptor(true), &pixels, generate_mipmaps))
+    }
+
+    /// Uploads `pixels` (tightly packed, matching `descriptor`'s pixel format/type) as a
+    /// new texture of size `width` x `height`, optionally generating mipmaps afterwards -
+    /// useful for populating a texture from in-memory pixel data that didn't come from
+    /// an image file (e.g. a procedurally generated mask or a decoded video frame).
+    pub fn from_raw(gl_context: Rc<Gl>, width: usize, height: usize, descriptor: TextureDescriptor, pixels: &[u8], generate_mipmaps: bool) -> Self {
+        Self::create(gl_context, width, height, descriptor, Some(pixels), generate_mipmaps)
+    }
+
+    /// Streams a sub-rectangle of pixel data into this texture at `(x, y)`, e.g. for
+    /// glyph atlas updates or streaming video frames. `pixels` must be tightly packed
+    /// and match this texture's `descriptor` pixel format/type.
+    pub fn upload_sub_region(&self, x: usize, y: usize, width: usize, height: usize, pixels: &[u8]) {
+        self.gl_context.bind_texture(gl::TEXTURE_2D, self.texture_id);
+        // `pixels` is documented as tightly packed - the default unpack alignment of 4
+        // would otherwise skew odd-width rows of single/two-channel data (glyph atlases,
+        // masks) since their row byte width isn't always a multiple of 4.
+        self.gl_context.pixel_store_i(gl::UNPACK_ALIGNMENT, 1);
+        self.gl_context.tex_sub_image_2d(gl::TEXTURE_2D, 0, x as i32, y as i32, width, height, self.descriptor.pixel_format, self.descriptor.pixel_type, pixels);
+    }
+
+    fn create(gl_context: Rc<Gl>, width: usize, height: usize, descriptor: TextureDescriptor, pixels: Option<&[u8]>, generate_mipmaps: bool) -> Self {
 
         let textures = gl_context.gen_textures(1);
         let texture_id = textures[0];
 
         gl_context.bind_texture(gl::TEXTURE_2D, texture_id);
-        gl_context.tex_image_2d(gl::TEXTURE_2D, 0, gl::RGBA as i32, width, height, 0, gl::RGBA, gl::UNSIGNED_BYTE, None);
+        // Same reasoning as `upload_sub_region` - `pixels` is tightly packed, so don't
+        // let the default unpack alignment of 4 skew odd-width R8/RG8 uploads.
+        gl_context.pixel_store_i(gl::UNPACK_ALIGNMENT, 1);
+        gl_context.tex_image_2d(gl::TEXTURE_2D, 0, descriptor.internal_format, width, height, 0, descriptor.pixel_format, descriptor.pixel_type, pixels);
+
+        gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, descriptor.mag_filter);
+        gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, descriptor.min_filter);
+        gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, descriptor.wrap_s);
+        gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, descriptor.wrap_t);
+
+        if let Some([r, g, b, a]) = descriptor.swizzle {
+            gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_R, r as GLint);
+            gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_G, g as GLint);
+            gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_B, b as GLint);
+            gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_A, a as GLint);
+        }
 
-        gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-        gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-        gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-        gl_context.tex_parameter_i(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        if generate_mipmaps {
+            gl_context.generate_mipmap(gl::TEXTURE_2D);
+        }
 
         Self {
             texture_id,
-            dimensions: (width, height),
+            width,
+            height,
+            descriptor,
             gl_context,
         }
     }
 
     /// Sets the current texture as the target for `gl::COLOR_ATTACHEMENT0`, so that
-    pub fn get_framebuffer<'a>(&'a self) -> FrameBuffer<'a> {
+    /// rendering can be directed into this texture instead of the window.
+    pub fn get_framebuffer<'a>(&'a self) -> Result<FrameBuffer<'a>, FrameBufferIncompleteError> {
 
         let fb = FrameBuffer::new(self);
 
+        // Bind the new FBO so the attachment/draw-buffer state below (and the
+        // completeness check further down) actually target it, not whatever was
+        // previously bound to GL_FRAMEBUFFER.
+        self.gl_context.bind_framebuffer(gl::FRAMEBUFFER, fb.id);
+
         // Set "textures[0]" as the color attachement #0
         self.gl_context.framebuffer_texture_2d(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.texture_id, 0);
         self.gl_context.draw_buffers(&[gl::COLOR_ATTACHMENT0]);
 
-        // Check that the framebuffer is complete
-        debug_assert!(gl_context.check_frame_buffer_status(gl::FRAMEBUFFER) == gl::FRAMEBUFFER_COMPLETE);
+        // Checked unconditionally, release builds included - this is exactly the "blank
+        // screen, no error" failure mode a swallowed debug_assert would let through. Only
+        // meaningful because `fb.id` is bound above - checking with the wrong FBO bound
+        // would just report on whatever target happened to be bound before this call.
+        let status = self.gl_context.check_frame_buffer_status(gl::FRAMEBUFFER);
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(FrameBufferIncompleteError { status });
+        }
+
+        Ok(fb)
+    }
+}
+
+/// Returned by `Texture::get_framebuffer` when `glCheckFramebufferStatus` reports
+/// anything other than `GL_FRAMEBUFFER_COMPLETE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameBufferIncompleteError {
+    pub status: GLuint,
+}
+
+/// Minimum severity (per `KHR_debug`) a driver debug message must have to be
+/// surfaced by `poll_gl_debug_messages`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GlDebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
 
-        fb
+impl GlDebugSeverity {
+    fn from_gl(severity: GLuint) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => GlDebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => GlDebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => GlDebugSeverity::Low,
+            _ => GlDebugSeverity::Notification,
+        }
+    }
+}
+
+/// Enables `GL_DEBUG_OUTPUT` (and `GL_DEBUG_OUTPUT_SYNCHRONOUS`, so messages line up with
+/// the GL call that produced them). Requires `KHR_debug` / GL 4.3+; harmless no-op otherwise.
+/// Call once at startup, then drain messages every frame with `poll_gl_debug_messages`.
+pub fn enable_gl_debug_output(gl_context: &Rc<Gl>) {
+    gl_context.enable(gl::DEBUG_OUTPUT);
+    gl_context.enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+}
+
+/// Messages fetched per `glGetDebugMessageLog` call by `poll_gl_debug_messages` - the
+/// log is drained in batches of this size rather than all at once.
+const DEBUG_MESSAGE_BATCH_SIZE: usize = 32;
+
+/// Drains the driver's debug message log and routes every message at or above
+/// `min_severity` through the crate's logging (`error!` for `High`, `warn!` for
+/// `Medium`, `debug!` otherwise), so driver-reported errors show up instead of
+/// silently producing a blank screen.
+///
+/// Note: this polls `glGetDebugMessageLog` instead of registering a real
+/// `glDebugMessageCallback` - gleam's `Gl` trait has no way to hand it a safe Rust
+/// closure across the FFI boundary, so the log is drained explicitly. Call this once
+/// per frame (after `enable_gl_debug_output`) to keep it from filling up.
+pub fn poll_gl_debug_messages(gl_context: &Rc<Gl>, min_severity: GlDebugSeverity) {
+    loop {
+        let messages = gl_context.get_debug_messages(DEBUG_MESSAGE_BATCH_SIZE as GLuint);
+        let fetched = messages.len();
+
+        for message in messages {
+            let severity = GlDebugSeverity::from_gl(message.severity);
+            if severity < min_severity {
+                continue;
+            }
+            match severity {
+                GlDebugSeverity::High => error!("[gl] {}", message.message),
+                GlDebugSeverity::Medium => warn!("[gl] {}", message.message),
+                GlDebugSeverity::Low | GlDebugSeverity::Notification => debug!("[gl] {}", message.message),
+            }
+        }
+
+        if fetched < DEBUG_MESSAGE_BATCH_SIZE {
+            break;
+        }
     }
 }
 
@@ -66,7 +349,7 @@ impl PartialEq for Texture {
     /// Note: Comparison uses only the OpenGL ID, it doesn't compare the
     /// actual contents of the texture.
     fn eq(&self, other: &Texture) -> bool {
-        self.texture_id == other.inner.texture_id
+        self.texture_id == other.texture_id
     }
 }
 
@@ -88,7 +371,7 @@ pub struct FrameBuffer<'a> {
 impl<'a> FrameBuffer<'a> {
 
     fn new(texture: &'a Texture) -> Self {
-        let framebuffers = gl_context.gen_framebuffers(1);
+        let framebuffers = texture.gl_context.gen_framebuffers(1);
 
         Self {
             id: framebuffers[0],
@@ -98,18 +381,103 @@ impl<'a> FrameBuffer<'a> {
 
     pub fn bind(&self) {
         self.texture.gl_context.bind_texture(gl::TEXTURE_2D, self.texture.texture_id);
-        self.texture.gl_context.bind_framebuffer(gl::FRAMEBUFFER, framebuffers[0]);
+        self.texture.gl_context.bind_framebuffer(gl::FRAMEBUFFER, self.id);
         self.texture.gl_context.viewport(0, 0, self.texture.width, self.texture.height);
     }
 
-    pub fn draw(&self, shader: GlShader, vertices: VertexBuffer) {
+    /// Binds this framebuffer, uses `shader`'s program, binds `vertices`'
+    /// VAO/VBO, configures its attributes, applies `uniforms`, and issues the
+    /// draw call - `draw_elements` / `draw_elements_instanced` if `vertices`
+    /// has an index buffer, `draw_arrays` / `draw_arrays_instanced` otherwise.
+    pub fn draw(&self, shader: &GlShader, vertices: &VertexBuffer, uniforms: &[(&str, UniformValue)]) {
+
+        let gl_context = &self.texture.gl_context;
+
+        self.bind();
+
+        gl_context.use_program(shader.shader_program);
+        gl_context.bind_vertex_array(vertices.vao_id);
+        gl_context.bind_buffer(gl::ARRAY_BUFFER, vertices.vbo_id);
+
+        for attribute in &vertices.attributes {
+            attribute.bind(gl_context, shader.shader_program);
+        }
+
+        for (name, value) in uniforms {
+            shader.set_uniform(name, *value);
+        }
+
+        match (vertices.index_buffer_id, vertices.instance_count) {
+            (Some(index_buffer_id), instance_count) if instance_count > 0 => {
+                gl_context.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer_id);
+                gl_context.draw_elements_instanced(gl::TRIANGLES, vertices.index_count as i32, gl::UNSIGNED_INT, 0, instance_count as i32);
+            },
+            (Some(index_buffer_id), _) => {
+                gl_context.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer_id);
+                gl_context.draw_elements(gl::TRIANGLES, vertices.index_count as i32, gl::UNSIGNED_INT, 0);
+            },
+            (None, instance_count) if instance_count > 0 => {
+                gl_context.draw_arrays_instanced(gl::TRIANGLES, 0, vertices.vertex_count as i32, instance_count as i32);
+            },
+            (None, _) => {
+                gl_context.draw_arrays(gl::TRIANGLES, 0, vertices.vertex_count as i32);
+            },
+        }
 
+        gl_context.bind_vertex_array(0);
     }
 
     pub fn unbind(&self) {
         self.texture.gl_context.bind_texture(gl::TEXTURE_2D, 0);
         self.texture.gl_context.bind_framebuffer(gl::FRAMEBUFFER, 0);
     }
+
+    /// Reads this framebuffer's pixels back to the CPU as a tightly-packed buffer
+    /// matching the backing texture's `width` / `height` and pixel format/type, with
+    /// rows flipped to top-left origin (`glReadPixels` itself returns bottom-left
+    /// origin rows). Useful for screenshot export and pixel-diff regression tests.
+    pub fn read_pixels(&self) -> Vec<u8> {
+        self.read_pixels_bytes(true)
+    }
+
+    /// Like `read_pixels`, but reinterprets the result as `f32` - use this for float
+    /// render targets (e.g. a `TextureFormat::Rgba16F` framebuffer).
+    pub fn read_pixels_f32(&self) -> Vec<f32> {
+        self.read_pixels_bytes(true)
+            .chunks_exact(4)
+            .map(|chunk| {
+                let mut bytes = [0u8; 4];
+                bytes.copy_from_slice(chunk);
+                f32::from_ne_bytes(bytes)
+            })
+            .collect()
+    }
+
+    fn read_pixels_bytes(&self, flip_rows: bool) -> Vec<u8> {
+
+        self.bind();
+
+        let width = self.texture.width;
+        let height = self.texture.height;
+        let pixels = self.texture.gl_context.read_pixels(
+            0, 0, width as i32, height as i32,
+            self.texture.descriptor.pixel_format,
+            self.texture.descriptor.pixel_type,
+        );
+
+        if !flip_rows || height == 0 {
+            return pixels;
+        }
+
+        let stride = pixels.len() / height;
+        let mut flipped = vec![0u8; pixels.len()];
+        for row in 0..height {
+            let src_start = row * stride;
+            let dst_start = (height - 1 - row) * stride;
+            flipped[dst_start..dst_start + stride].copy_from_slice(&pixels[src_start..src_start + stride]);
+        }
+        flipped
+    }
 }
 
 impl<'a> Drop for FrameBuffer<'a> {
@@ -118,15 +486,189 @@ impl<'a> Drop for FrameBuffer<'a> {
     }
 }
 
-#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// Describes the layout of one vertex attribute inside a `VertexBuffer`'s
+/// buffer, resolved against a `GlShader`'s attribute locations at draw time.
+#[derive(Debug, Clone)]
+pub struct VertexAttribute {
+    /// Name of the `in` variable in the vertex shader
+    pub name: String,
+    /// Number of components per vertex (1 - 4)
+    pub component_count: i32,
+    /// `gl::FLOAT`, `gl::UNSIGNED_BYTE`, `gl::INT`, etc.
+    pub gl_type: GLuint,
+    /// Whether integer types should be normalized to `[0, 1]` / `[-1, 1]`
+    pub normalized: bool,
+    /// Byte offset between consecutive vertices
+    pub stride: i32,
+    /// Byte offset of this attribute within a vertex
+    pub offset: i32,
+    /// `0` to advance once per vertex, `n` to advance once every `n` instances
+    pub divisor: u32,
+}
+
+impl VertexAttribute {
+
+    pub fn new(name: &str, component_count: i32, gl_type: GLuint, normalized: bool, stride: i32, offset: i32) -> Self {
+        Self { name: name.to_string(), component_count, gl_type, normalized, stride, offset, divisor: 0 }
+    }
+
+    /// Turns this into an instanced attribute, advancing once every `divisor` instances.
+    pub fn with_divisor(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+
+    fn bind(&self, gl_context: &Rc<Gl>, program: GLuint) {
+
+        let location = gl_context.get_attrib_location(program, &self.name);
+        if location < 0 {
+            return;
+        }
+        let location = location as GLuint;
+
+        match self.gl_type {
+            gl::BYTE | gl::UNSIGNED_BYTE | gl::SHORT | gl::UNSIGNED_SHORT | gl::INT | gl::UNSIGNED_INT if !self.normalized => {
+                gl_context.vertex_attrib_i_pointer(location, self.component_count, self.gl_type, self.stride, self.offset as u32);
+            },
+            _ => {
+                gl_context.vertex_attrib_pointer(location, self.component_count, self.gl_type, self.normalized, self.stride, self.offset as u32);
+            },
+        }
+
+        gl_context.enable_vertex_attrib_array(location);
+
+        if self.divisor != 0 {
+            gl_context.vertex_attrib_divisor(location, self.divisor);
+        }
+    }
+}
+
+/// A GPU-side vertex buffer (plus an optional index buffer), ready to be
+/// rendered via `FrameBuffer::draw`.
+#[derive(Debug)]
+pub struct VertexBuffer {
+    vao_id: GLuint,
+    vbo_id: GLuint,
+    index_buffer_id: Option<GLuint>,
+    attributes: Vec<VertexAttribute>,
+    vertex_count: usize,
+    index_count: usize,
+    instance_count: usize,
+    gl_context: Rc<Gl>,
+}
+
+impl VertexBuffer {
+
+    /// Uploads `vertices` (a flat buffer of `f32`s) and lays it out according to `attributes`.
+    pub fn new(gl_context: Rc<Gl>, vertices: &[f32], vertex_count: usize, attributes: Vec<VertexAttribute>) -> Self {
+
+        let vao_id = gl_context.gen_vertex_arrays(1)[0];
+        let vbo_id = gl_context.gen_buffers(1)[0];
+
+        gl_context.bind_vertex_array(vao_id);
+        gl_context.bind_buffer(gl::ARRAY_BUFFER, vbo_id);
+        gl_context.buffer_data_untyped(
+            gl::ARRAY_BUFFER,
+            (vertices.len() * mem::size_of::<f32>()) as isize,
+            vertices.as_ptr() as *const _,
+            gl::STATIC_DRAW,
+        );
+        gl_context.bind_vertex_array(0);
+
+        Self {
+            vao_id,
+            vbo_id,
+            index_buffer_id: None,
+            attributes,
+            vertex_count,
+            index_count: 0,
+            instance_count: 0,
+            gl_context,
+        }
+    }
+
+    /// Attaches an index buffer, switching subsequent draws to `draw_elements(_instanced)`.
+    pub fn with_indices(mut self, indices: &[u32]) -> Self {
+
+        let index_buffer_id = self.gl_context.gen_buffers(1)[0];
+
+        self.gl_context.bind_vertex_array(self.vao_id);
+        self.gl_context.bind_buffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer_id);
+        self.gl_context.buffer_data_untyped(
+            gl::ELEMENT_ARRAY_BUFFER,
+            (indices.len() * mem::size_of::<u32>()) as isize,
+            indices.as_ptr() as *const _,
+            gl::STATIC_DRAW,
+        );
+        self.gl_context.bind_vertex_array(0);
+
+        self.index_buffer_id = Some(index_buffer_id);
+        self.index_count = indices.len();
+        self
+    }
+
+    /// Marks this buffer for instanced rendering with `instance_count` instances.
+    pub fn with_instance_count(mut self, instance_count: usize) -> Self {
+        self.instance_count = instance_count;
+        self
+    }
+}
+
+impl Drop for VertexBuffer {
+    fn drop(&mut self) {
+        self.gl_context.delete_buffers(&[self.vbo_id]);
+        if let Some(index_buffer_id) = self.index_buffer_id {
+            self.gl_context.delete_buffers(&[index_buffer_id]);
+        }
+        self.gl_context.delete_vertex_arrays(&[self.vao_id]);
+    }
+}
+
+/// A value that can be pushed into a shader uniform via `GlShader::set_uniform`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Vec2([f32; 2]),
+    Vec3([f32; 3]),
+    Vec4([f32; 4]),
+    Mat4([f32; 16]),
+    Int(i32),
+    /// Texture unit index to bind a sampler uniform to, e.g. `0` for `gl::TEXTURE0`.
+    Sampler(i32),
+}
+
+#[derive(Debug)]
 pub struct GlShader {
     pub shader_program: GLuint,
     pub gl_context: Rc<Gl>,
+    /// Set when this shader was created via `GlShader::from_files` - lets
+    /// `poll_reload()` know which files to watch and what their last-seen
+    /// modification times were.
+    reload_source: Option<ShaderReloadSource>,
+    /// Caches `name -> glGetUniformLocation(...)` lookups done by `set_uniform`.
+    uniform_locations: RefCell<HashMap<String, GLint>>,
+}
+
+/// Tracks the on-disk origin of a live-reloadable shader.
+#[derive(Debug, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+struct ShaderReloadSource {
+    vertex_path: PathBuf,
+    fragment_path: PathBuf,
+    vertex_modified: SystemTime,
+    fragment_modified: SystemTime,
+}
+
+/// Error returned by `GlShader::from_files` when a source file can't be read
+/// from disk, in addition to the usual compile / link failures.
+#[derive(Debug)]
+pub enum GlShaderFromFileError {
+    Io(::std::io::Error),
+    Create(GlShaderCreateError),
 }
 
 impl Drop for GlShader {
     fn drop(&mut self) {
-        self.context.delete_program(self.shader_program);
+        self.gl_context.delete_program(self.shader_program);
     }
 }
 
@@ -160,12 +702,91 @@ pub enum GlShaderCreateError {
     Link(GlShaderLinkError),
 }
 
+/// On-disk cache of linked program binaries, keyed by a hash of their combined source
+/// and the driver's renderer/version string - so `GlShader::with_cache` can skip
+/// compiling and linking on a repeat run, while a changed source or a different driver
+/// (different renderer/version string) transparently misses and falls back to a normal
+/// compile instead of loading an incompatible binary.
+#[derive(Debug, Clone)]
+pub struct ProgramBinaryCache {
+    directory: PathBuf,
+}
+
+impl ProgramBinaryCache {
+
+    pub fn new<P: AsRef<Path>>(directory: P) -> Self {
+        Self { directory: directory.as_ref().to_path_buf() }
+    }
+
+    fn digest(&self, gl_context: &Rc<Gl>, vertex_source: &str, fragment_source: &str) -> String {
+        let mut hasher = DefaultHasher::new();
+        vertex_source.hash(&mut hasher);
+        fragment_source.hash(&mut hasher);
+        gl_context.get_string(gl::RENDERER).hash(&mut hasher);
+        gl_context.get_string(gl::VERSION).hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+
+    fn path_for(&self, digest: &str) -> PathBuf {
+        self.directory.join(format!("{}.bin", digest))
+    }
+
+    /// Tries to load a cached binary into `program` via `glProgramBinary`. Returns
+    /// `false` (leaving `program` unlinked) on any cache miss or link failure, so the
+    /// caller can fall back to compiling from source.
+    fn load(&self, gl_context: &Rc<Gl>, digest: &str, program: GLuint) -> bool {
+
+        let bytes = match fs::read(self.path_for(digest)) {
+            Ok(bytes) => bytes,
+            Err(_) => return false,
+        };
+
+        if bytes.len() < mem::size_of::<u32>() {
+            return false;
+        }
+
+        let (format_bytes, binary) = bytes.split_at(mem::size_of::<u32>());
+        let format = u32::from_le_bytes([format_bytes[0], format_bytes[1], format_bytes[2], format_bytes[3]]);
+
+        gl_context.program_binary(program, format, binary);
+
+        get_gl_program_error(gl_context, program).is_none()
+    }
+
+    /// Stores `program`'s linked binary (via `glGetProgramBinary`) under `digest`.
+    fn store(&self, gl_context: &Rc<Gl>, digest: &str, program: GLuint) {
+
+        let (binary, format) = gl_context.get_program_binary(program);
+        if binary.is_empty() {
+            return;
+        }
+
+        if fs::create_dir_all(&self.directory).is_err() {
+            return;
+        }
+
+        let mut bytes = Vec::with_capacity(mem::size_of::<u32>() + binary.len());
+        bytes.extend_from_slice(&format.to_le_bytes());
+        bytes.extend_from_slice(&binary);
+
+        let _ = fs::write(self.path_for(digest), bytes);
+    }
+}
+
 impl GlShader {
 
     /// Compiles and creates a new OpenGL shader, created from a vertex and a fragment shader string.
     ///
     /// If the shader fails to compile, the shader object gets automatically deleted, no cleanup necessary.
     pub fn new(context: Rc<Gl>, vertex_shader_source: &str, fragment_shader_source: &str) -> Result<Self, GlShaderCreateError> {
+        Self::compile(context, vertex_shader_source, fragment_shader_source, false)
+    }
+
+    /// Shared compile/link path behind `new` and `with_cache`'s cache-miss fallback.
+    /// `retrievable_hint` sets `GL_PROGRAM_BINARY_RETRIEVABLE_HINT` before linking -
+    /// without it, conforming drivers are allowed to return a zero-length binary from
+    /// `glGetProgramBinary`, so `with_cache` needs this set to actually populate its cache.
+    fn compile(context: Rc<Gl>, vertex_shader_source: &str, fragment_shader_source: &str, retrievable_hint: bool) -> Result<Self, GlShaderCreateError> {
 
         // Compile vertex shader
 
@@ -173,12 +794,12 @@ impl GlShader {
         context.shader_source(vertex_shader_object, &[vertex_shader_source]);
         context.compile_shader(vertex_shader_object);
 
-        #[cfg(debug_assertions)] {
-            if let Some(error_id) = get_gl_shader_error(context, vertex_shader_object) {
-                let info_log = context.get_shader_info_log(vertex_shader_object);
-                context.delete_shader(vertex_shader_object);
-                return Err(GlShaderCreateError::Compile(GlShaderCompileError::Vertex(VertexShaderCompileError { error_id, info_log })));
-            }
+        // Checked unconditionally, release builds included - silently linking a broken
+        // program just trades a compile error for a blank screen further down the line.
+        if let Some(error_id) = get_gl_shader_error(&context, vertex_shader_object) {
+            let info_log = context.get_shader_info_log(vertex_shader_object);
+            context.delete_shader(vertex_shader_object);
+            return Err(GlShaderCreateError::Compile(GlShaderCompileError::Vertex(VertexShaderCompileError { error_id, info_log })));
         }
 
         // Compile fragment shader
@@ -187,13 +808,11 @@ impl GlShader {
         context.shader_source(fragment_shader_object, &[fragment_shader_source]);
         context.compile_shader(fragment_shader_object);
 
-        #[cfg(debug_assertions)] {
-            if let Some(error_id) = get_gl_shader_error(context, fragment_shader_object) {
-                let info_log = context.get_shader_info_log(fragment_shader_object);
-                context.delete_shader(vertex_shader_object);
-                context.delete_shader(fragment_shader_object);
-                return Err(GlShaderCreateError::Compile(GlShaderCompileError::Fragment(FragmentShaderCompileError { error_id, info_log })));
-            }
+        if let Some(error_id) = get_gl_shader_error(&context, fragment_shader_object) {
+            let info_log = context.get_shader_info_log(fragment_shader_object);
+            context.delete_shader(vertex_shader_object);
+            context.delete_shader(fragment_shader_object);
+            return Err(GlShaderCreateError::Compile(GlShaderCompileError::Fragment(FragmentShaderCompileError { error_id, info_log })));
         }
 
         // Link program
@@ -201,29 +820,176 @@ impl GlShader {
         let program = context.create_program();
         context.attach_shader(program, vertex_shader_object);
         context.attach_shader(program, fragment_shader_object);
+        if retrievable_hint {
+            context.program_parameter_i(program, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
+        }
         context.link_program(program);
 
-        #[cfg(debug_assertions)] {
-            if let Some(error_id) = get_gl_program_error(context, program) {
-                let info_log = context.get_program_info_log(program);
-                context.delete_shader(vertex_shader_object);
-                context.delete_shader(fragment_shader_object);
-                context.delete_program(program);
-                return Err(GlShaderCreateError::Link(GlShaderLinkError { error_id, info_log }));
-            }
+        if let Some(error_id) = get_gl_program_error(&context, program) {
+            let info_log = context.get_program_info_log(program);
+            context.delete_shader(vertex_shader_object);
+            context.delete_shader(fragment_shader_object);
+            context.delete_program(program);
+            return Err(GlShaderCreateError::Link(GlShaderLinkError { error_id, info_log }));
         }
 
         context.delete_shader(vertex_shader_object);
         context.delete_shader(fragment_shader_object);
 
-        Some(GlShader {
+        Ok(GlShader {
             shader_program: program,
             gl_context: context,
+            reload_source: None,
+            uniform_locations: RefCell::new(HashMap::new()),
         })
     }
+
+    /// Like `new`, but first tries to load a previously-linked program binary from
+    /// `cache` instead of compiling from scratch, and stores a freshly linked program
+    /// back into `cache` on a miss. The cache key folds in the driver's renderer/version
+    /// string, so a cache built by a different driver simply misses rather than loading
+    /// an incompatible binary. Cuts repeated cold-start shader build time.
+    pub fn with_cache(context: Rc<Gl>, vertex_shader_source: &str, fragment_shader_source: &str, cache: &ProgramBinaryCache) -> Result<Self, GlShaderCreateError> {
+
+        let digest = cache.digest(&context, vertex_shader_source, fragment_shader_source);
+
+        let program = context.create_program();
+        if cache.load(&context, &digest, program) {
+            return Ok(GlShader {
+                shader_program: program,
+                gl_context: context,
+                reload_source: None,
+                uniform_locations: RefCell::new(HashMap::new()),
+            });
+        }
+        context.delete_program(program);
+
+        let shader = Self::compile(context, vertex_shader_source, fragment_shader_source, true)?;
+        cache.store(&shader.gl_context, &digest, shader.shader_program);
+        Ok(shader)
+    }
+
+    /// Sets a uniform on this shader's program, looking up (and caching) its
+    /// location by name. Does nothing if the shader doesn't declare a uniform
+    /// with this name (the driver may have optimized it out).
+    pub fn set_uniform(&self, name: &str, value: UniformValue) {
+
+        let location = self.uniform_location(name);
+        if location < 0 {
+            return;
+        }
+
+        self.gl_context.use_program(self.shader_program);
+
+        match value {
+            UniformValue::Float(v) => self.gl_context.uniform_1f(location, v),
+            UniformValue::Vec2(v) => self.gl_context.uniform_2f(location, v[0], v[1]),
+            UniformValue::Vec3(v) => self.gl_context.uniform_3f(location, v[0], v[1], v[2]),
+            UniformValue::Vec4(v) => self.gl_context.uniform_4f(location, v[0], v[1], v[2], v[3]),
+            UniformValue::Mat4(v) => self.gl_context.uniform_matrix_4fv(location, false, &v),
+            UniformValue::Int(v) => self.gl_context.uniform_1i(location, v),
+            UniformValue::Sampler(unit) => self.gl_context.uniform_1i(location, unit),
+        }
+    }
+
+    fn uniform_location(&self, name: &str) -> GLint {
+
+        if let Some(location) = self.uniform_locations.borrow().get(name) {
+            return *location;
+        }
+
+        let location = self.gl_context.get_uniform_location(self.shader_program, name);
+        self.uniform_locations.borrow_mut().insert(name.to_string(), location);
+        location
+    }
+
+    /// Compiles a shader from two `.glsl` files on disk instead of in-memory
+    /// strings, and remembers their paths and last-modified timestamps so that
+    /// `poll_reload()` can recompile and relink the program whenever either
+    /// file changes - handy for iterating on shaders without restarting the app.
+    pub fn from_files<P: AsRef<Path>>(context: Rc<Gl>, vertex_path: P, fragment_path: P) -> Result<Self, GlShaderFromFileError> {
+
+        let vertex_path = vertex_path.as_ref().to_path_buf();
+        let fragment_path = fragment_path.as_ref().to_path_buf();
+
+        let vertex_modified = modified_time(&vertex_path).map_err(GlShaderFromFileError::Io)?;
+        let fragment_modified = modified_time(&fragment_path).map_err(GlShaderFromFileError::Io)?;
+
+        let vertex_source = fs::read_to_string(&vertex_path).map_err(GlShaderFromFileError::Io)?;
+        let fragment_source = fs::read_to_string(&fragment_path).map_err(GlShaderFromFileError::Io)?;
+
+        let mut shader = Self::new(context, &vertex_source, &fragment_source)
+            .map_err(GlShaderFromFileError::Create)?;
+
+        shader.reload_source = Some(ShaderReloadSource {
+            vertex_path,
+            fragment_path,
+            vertex_modified,
+            fragment_modified,
+        });
+
+        Ok(shader)
+    }
+
+    /// Checks the modification time of the files this shader was created from
+    /// (via `from_files`) and, if either one has changed since the last check,
+    /// recompiles and relinks the program from the files' current contents.
+    ///
+    /// Returns `Ok(true)` if the program was reloaded, `Ok(false)` if nothing
+    /// had changed (or this shader wasn't created from files, or a changed file
+    /// couldn't be read - it is then checked again on the next call). On a compile or
+    /// link failure the previously working `shader_program` is left untouched -
+    /// callers are expected to log the returned error and keep rendering with
+    /// the old program rather than falling back to a blank screen.
+    pub fn poll_reload(&mut self) -> Result<bool, GlShaderCreateError> {
+
+        let (vertex_path, fragment_path, vertex_modified, fragment_modified) = match &self.reload_source {
+            Some(source) => (
+                source.vertex_path.clone(),
+                source.fragment_path.clone(),
+                modified_time(&source.vertex_path).ok(),
+                modified_time(&source.fragment_path).ok(),
+            ),
+            None => return Ok(false),
+        };
+
+        let reload_source = self.reload_source.as_ref().unwrap();
+        let vertex_changed = vertex_modified.map_or(false, |m| m != reload_source.vertex_modified);
+        let fragment_changed = fragment_modified.map_or(false, |m| m != reload_source.fragment_modified);
+
+        if !vertex_changed && !fragment_changed {
+            return Ok(false);
+        }
+
+        let vertex_source = match fs::read_to_string(&vertex_path) { Ok(s) => s, Err(_) => return Ok(false) };
+        let fragment_source = match fs::read_to_string(&fragment_path) { Ok(s) => s, Err(_) => return Ok(false) };
+
+        let new_shader = Self::new(self.gl_context.clone(), &vertex_source, &fragment_source)?;
+        let new_program = new_shader.shader_program;
+        // `new_shader`'s `Drop` impl would delete `new_program` again once it
+        // goes out of scope - it's being adopted by `self`, not discarded.
+        ::std::mem::forget(new_shader);
+
+        // Swap in the freshly linked program and delete the old one - only
+        // reached once compilation and linking both succeeded.
+        self.gl_context.delete_program(self.shader_program);
+        self.shader_program = new_program;
+        // Uniform locations aren't stable across a relink - drop the cache so
+        // `set_uniform` re-resolves them against the new program.
+        self.uniform_locations.borrow_mut().clear();
+
+        let reload_source = self.reload_source.as_mut().unwrap();
+        if let Some(m) = vertex_modified { reload_source.vertex_modified = m; }
+        if let Some(m) = fragment_modified { reload_source.fragment_modified = m; }
+
+        Ok(true)
+    }
+}
+
+fn modified_time<P: AsRef<Path>>(path: P) -> Result<SystemTime, ::std::io::Error> {
+    fs::metadata(path)?.modified()
 }
 
-#[cfg(debug_assertions)]
 fn get_gl_shader_error(context: &Gl, shader_object: GLuint) -> Option<usize> {
     let mut err = [0];
     unsafe { context.get_shader_iv(shader_object, gl::COMPILE_STATUS, &mut err) };
@@ -231,7 +997,6 @@ fn get_gl_shader_error(context: &Gl, shader_object: GLuint) -> Option<usize> {
     if err_code == 0 { None } else { Some(err_code) }
 }
 
-#[cfg(debug_assertions)]
 fn get_gl_program_error(context: &Gl, shader_object: GLuint) -> Option<usize> {
     let mut err = [0];
     unsafe { context.get_program_iv(shader_object, gl::LINK_STATUS, &mut err) };